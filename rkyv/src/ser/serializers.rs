@@ -1,74 +1,87 @@
 //! Serializers that can be used standalone and provide basic capabilities.
 
+#[cfg(any(feature = "std", feature = "alloc"))]
+use crate::AlignedVec;
 use crate::{
     ser::{SeekSerializer, Serializer},
     Fallible,
 };
-use core::ptr;
+#[cfg(any(feature = "std", feature = "alloc"))]
+use crate::Serialize;
+use core::{alloc::Layout, ptr, ptr::NonNull};
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{
+    alloc::{alloc, dealloc},
+    vec::Vec,
+};
 #[cfg(feature = "std")]
 use std::io;
 
-/// Wraps a byte buffer and equips it with [`Serializer`].
-///
-/// Common uses include archiving in `#![no_std]` environments and archiving
-/// small objects without allocating.
-///
-/// ## Examples
-/// ```
-/// use rkyv::{
-///     archived_value,
-///     ser::{Serializer, serializers::BufferSerializer},
-///     Aligned,
-///     Archive,
-///     Archived,
-///     Serialize,
-/// };
-///
-/// #[derive(Archive, Serialize)]
-/// enum Event {
-///     Spawn,
-///     Speak(String),
-///     Die,
-/// }
+/// A sink that a [`GenericSerializer`] can write bytes into.
 ///
-/// let mut serializer = BufferSerializer::new(Aligned([0u8; 256]));
-/// let pos = serializer.serialize_value(&Event::Speak("Help me!".to_string()))
-///     .expect("failed to archive event");
-/// let buf = serializer.into_inner();
-/// let archived = unsafe { archived_value::<Event>(buf.as_ref(), pos) };
-/// if let Archived::<Event>::Speak(message) = archived {
-///     assert_eq!(message.as_str(), "Help me!");
-/// } else {
-///     panic!("archived event was of the wrong type");
-/// }
-/// ```
-pub struct BufferSerializer<T> {
-    inner: T,
-    pos: usize,
+/// This unifies the slice-backed storage used by [`BufferSerializer`] and
+/// the [`io::Write`](std::io::Write)-backed storage used by
+/// [`WriteSerializer`] behind one trait, so `GenericSerializer` only has to
+/// implement `Serializer`'s `pos`/`write`/`pad` once for both.
+pub trait Write {
+    /// The error type returned when a write to this sink fails.
+    type Error;
+
+    /// Writes `bytes` starting at `pos`.
+    fn write_bytes(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Writes `padding` zero bytes starting at `pos`.
+    fn pad_bytes(&mut self, pos: usize, padding: usize) -> Result<(), Self::Error>;
 }
 
-impl<T> BufferSerializer<T> {
-    /// Creates a new archive buffer from a byte buffer.
+/// A [`Write`] sink that can also be sought to an arbitrary position.
+pub trait Seek: Write {
+    /// Seeks the sink to `pos`.
+    fn seek_bytes(&mut self, pos: usize) -> Result<(), Self::Error>;
+}
+
+/// Wraps a byte buffer so it can be used as a [`Write`]/[`Seek`] sink for a
+/// [`GenericSerializer`].
+///
+/// `SliceBuffer` is a thin wrapper rather than a blanket impl of [`Write`]
+/// for every `T: AsRef<[u8]> + AsMut<[u8]>`, because such a blanket impl
+/// would conflict with the one for [`io::Write`](std::io::Write) sinks --
+/// some buffers (like `Vec<u8>`) implement both.
+pub struct SliceBuffer<T>(T);
+
+impl<T> SliceBuffer<T> {
+    /// Wraps the given buffer.
     pub fn new(inner: T) -> Self {
-        Self::with_pos(inner, 0)
+        Self(inner)
     }
 
-    /// Creates a new archive buffer from a byte buffer. The buffer will start
-    /// writing at the given position, but the buffer must contain all bytes
-    /// (otherwise the alignments of types may not be correct).
-    pub fn with_pos(inner: T, pos: usize) -> Self {
-        Self { inner, pos }
+    /// Consumes the wrapper and returns the buffer used to create it.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for SliceBuffer<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
     }
+}
 
-    /// Consumes the buffer and returns the internal buffer used to create it.
-    pub fn into_inner(self) -> T {
-        self.inner
+impl<T: AsMut<[u8]>> AsMut<[u8]> for SliceBuffer<T> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.0.as_mut()
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> From<T> for SliceBuffer<T> {
+    fn from(inner: T) -> Self {
+        Self::new(inner)
     }
 }
 
-/// The error type returned by an [`BufferSerializer`].
+/// The error type returned by a [`SliceBuffer`].
 #[derive(Debug)]
-pub enum BufferSerializerError {
+pub enum SliceBufferError {
     /// Writing has overflowed the internal buffer.
     Overflow {
         pos: usize,
@@ -82,21 +95,15 @@ pub enum BufferSerializerError {
     },
 }
 
-impl<T: AsRef<[u8]> + AsMut<[u8]>> Fallible for BufferSerializer<T> {
-    type Error = BufferSerializerError;
-}
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Write for SliceBuffer<T> {
+    type Error = SliceBufferError;
 
-impl<T: AsRef<[u8]> + AsMut<[u8]>> Serializer for BufferSerializer<T> {
-    fn pos(&self) -> usize {
-        self.pos
-    }
-
-    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
-        let end_pos = self.pos + bytes.len();
-        let archive_len = self.inner.as_ref().len();
+    fn write_bytes(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+        let end_pos = pos + bytes.len();
+        let archive_len = self.0.as_ref().len();
         if end_pos > archive_len {
-            Err(BufferSerializerError::Overflow {
-                pos: self.pos,
+            Err(SliceBufferError::Overflow {
+                pos,
                 bytes_needed: bytes.len(),
                 archive_len,
             })
@@ -104,46 +111,176 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Serializer for BufferSerializer<T> {
             unsafe {
                 ptr::copy_nonoverlapping(
                     bytes.as_ptr(),
-                    self.inner.as_mut().as_mut_ptr().add(self.pos),
+                    self.0.as_mut().as_mut_ptr().add(pos),
                     bytes.len(),
                 );
             }
-            self.pos = end_pos;
             Ok(())
         }
     }
 
-    fn pad(&mut self, padding: usize) -> Result<(), Self::Error> {
-        let end_pos = self.pos + padding;
-        let archive_len = self.inner.as_ref().len();
+    fn pad_bytes(&mut self, pos: usize, padding: usize) -> Result<(), Self::Error> {
+        let end_pos = pos + padding;
+        let archive_len = self.0.as_ref().len();
         if end_pos > archive_len {
-            Err(BufferSerializerError::Overflow {
-                pos: self.pos,
+            Err(SliceBufferError::Overflow {
+                pos,
                 bytes_needed: padding,
                 archive_len,
             })
         } else {
-            self.pos = end_pos;
+            for byte in &mut self.0.as_mut()[pos..end_pos] {
+                *byte = 0;
+            }
             Ok(())
         }
     }
 }
 
-impl<T: AsRef<[u8]> + AsMut<[u8]>> SeekSerializer for BufferSerializer<T> {
-    fn seek(&mut self, pos: usize) -> Result<(), Self::Error> {
-        let len = self.inner.as_ref().len();
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Seek for SliceBuffer<T> {
+    fn seek_bytes(&mut self, pos: usize) -> Result<(), Self::Error> {
+        let len = self.0.as_ref().len();
         if pos > len {
-            Err(BufferSerializerError::SoughtPastEnd {
+            Err(SliceBufferError::SoughtPastEnd {
                 seek_position: pos,
                 archive_len: len,
             })
         } else {
-            self.pos = pos;
             Ok(())
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl<W: io::Write> Write for W {
+    type Error = io::Error;
+
+    fn write_bytes(&mut self, _pos: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write_all(bytes)
+    }
+
+    fn pad_bytes(&mut self, _pos: usize, padding: usize) -> Result<(), Self::Error> {
+        const ZEROS: [u8; 16] = [0; 16];
+        let mut remaining = padding;
+        while remaining > 0 {
+            let n = remaining.min(ZEROS.len());
+            self.write_all(&ZEROS[..n])?;
+            remaining -= n;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write + io::Seek> Seek for W {
+    fn seek_bytes(&mut self, pos: usize) -> Result<(), Self::Error> {
+        io::Seek::seek(self, io::SeekFrom::Start(pos as u64))?;
+        Ok(())
+    }
+}
+
+/// A serializer built from any [`Write`] sink, tracking its own write
+/// position.
+///
+/// This is the single implementation shared by [`BufferSerializer`] (a
+/// `GenericSerializer<SliceBuffer<T>>`) and [`WriteSerializer`] (a
+/// `GenericSerializer<W>`), which are kept as type aliases for backward
+/// compatibility.
+pub struct GenericSerializer<W: Write> {
+    inner: W,
+    pos: usize,
+}
+
+impl<W: Write> GenericSerializer<W> {
+    /// Creates a new serializer from a sink.
+    pub fn new(inner: impl Into<W>) -> Self {
+        Self::with_pos(inner, 0)
+    }
+
+    /// Creates a new serializer from a sink, and assumes that the sink is
+    /// currently at the given position.
+    pub fn with_pos(inner: impl Into<W>, pos: usize) -> Self {
+        Self {
+            inner: inner.into(),
+            pos,
+        }
+    }
+
+    /// Consumes the serializer and returns the sink used to create it.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Fallible for GenericSerializer<W> {
+    type Error = W::Error;
+}
+
+impl<W: Write> Serializer for GenericSerializer<W> {
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.inner.write_bytes(self.pos, bytes)?;
+        self.pos += bytes.len();
+        Ok(())
+    }
+
+    fn pad(&mut self, padding: usize) -> Result<(), Self::Error> {
+        self.inner.pad_bytes(self.pos, padding)?;
+        self.pos += padding;
+        Ok(())
+    }
+}
+
+impl<W: Seek> SeekSerializer for GenericSerializer<W> {
+    fn seek(&mut self, pos: usize) -> Result<(), Self::Error> {
+        self.inner.seek_bytes(pos)?;
+        self.pos = pos;
+        Ok(())
+    }
+}
+
+/// Wraps a byte buffer and equips it with [`Serializer`].
+///
+/// Common uses include archiving in `#![no_std]` environments and archiving
+/// small objects without allocating.
+///
+/// ## Examples
+/// ```
+/// use rkyv::{
+///     archived_value,
+///     ser::{Serializer, serializers::BufferSerializer},
+///     Aligned,
+///     Archive,
+///     Archived,
+///     Serialize,
+/// };
+///
+/// #[derive(Archive, Serialize)]
+/// enum Event {
+///     Spawn,
+///     Speak(String),
+///     Die,
+/// }
+///
+/// let mut serializer = BufferSerializer::new(Aligned([0u8; 256]));
+/// let pos = serializer.serialize_value(&Event::Speak("Help me!".to_string()))
+///     .expect("failed to archive event");
+/// let buf = serializer.into_inner();
+/// let archived = unsafe { archived_value::<Event>(buf.as_ref(), pos) };
+/// if let Archived::<Event>::Speak(message) = archived {
+///     assert_eq!(message.as_str(), "Help me!");
+/// } else {
+///     panic!("archived event was of the wrong type");
+/// }
+/// ```
+pub type BufferSerializer<T> = GenericSerializer<SliceBuffer<T>>;
+
+/// The error type returned by a [`BufferSerializer`].
+pub type BufferSerializerError = SliceBufferError;
+
 /// Wraps a type that implements [`io::Write`](std::io::Write) and equips it
 /// with [`Serializer`].
 ///
@@ -160,13 +297,33 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> SeekSerializer for BufferSerializer<T> {
 /// assert_eq!(buf, vec![0u8, 1u8, 2u8, 3u8]);
 /// ```
 #[cfg(feature = "std")]
-pub struct WriteSerializer<W: io::Write> {
+pub type WriteSerializer<W> = GenericSerializer<W>;
+
+/// Wraps a type that implements [`embedded_io::Write`] and equips it with
+/// [`Serializer`].
+///
+/// This is the `#![no_std]` counterpart to [`WriteSerializer`], for streaming
+/// output to sinks like a UART, a flash writer, or a network socket when no
+/// allocator is available.
+///
+/// ## Examples
+/// ```
+/// use rkyv::ser::{serializers::EmbeddedIoSerializer, Serializer};
+///
+/// let mut bytes = [0u8; 4];
+/// let mut serializer = EmbeddedIoSerializer::new(&mut bytes[..]);
+/// assert_eq!(serializer.pos(), 0);
+/// serializer.write(&[0u8, 1u8, 2u8, 3u8]);
+/// assert_eq!(serializer.pos(), 4);
+/// ```
+#[cfg(feature = "embedded-io")]
+pub struct EmbeddedIoSerializer<W: embedded_io::Write> {
     inner: W,
     pos: usize,
 }
 
-#[cfg(feature = "std")]
-impl<W: io::Write> WriteSerializer<W> {
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> EmbeddedIoSerializer<W> {
     /// Creates a new serializer from a writer.
     pub fn new(inner: W) -> Self {
         Self::with_pos(inner, 0)
@@ -185,28 +342,1005 @@ impl<W: io::Write> WriteSerializer<W> {
     }
 }
 
-#[cfg(feature = "std")]
-impl<W: io::Write> Fallible for WriteSerializer<W> {
-    type Error = io::Error;
+/// The error type returned by an [`EmbeddedIoSerializer`].
+#[cfg(feature = "embedded-io")]
+#[derive(Debug)]
+pub enum EmbeddedIoSerializerError<E> {
+    /// The underlying writer returned an error.
+    Io(E),
+    /// The underlying writer accepted zero bytes without returning an
+    /// error, so no progress could be made.
+    WriteZero,
 }
 
-#[cfg(feature = "std")]
-impl<W: io::Write> Serializer for WriteSerializer<W> {
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> Fallible for EmbeddedIoSerializer<W> {
+    type Error = EmbeddedIoSerializerError<W::Error>;
+}
+
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> Serializer for EmbeddedIoSerializer<W> {
     fn pos(&self) -> usize {
         self.pos
     }
 
-    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
-        self.pos += self.inner.write(bytes)?;
+    fn write(&mut self, mut bytes: &[u8]) -> Result<(), Self::Error> {
+        while !bytes.is_empty() {
+            match self.inner.write(bytes) {
+                Ok(0) => return Err(EmbeddedIoSerializerError::WriteZero),
+                Ok(n) => {
+                    self.pos += n;
+                    bytes = &bytes[n..];
+                }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        embedded_io::ErrorKind::Interrupted
+                            | embedded_io::ErrorKind::WouldBlock
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(EmbeddedIoSerializerError::Io(e)),
+            }
+        }
         Ok(())
     }
 }
 
-#[cfg(feature = "std")]
-impl<W: io::Write + io::Seek> SeekSerializer for WriteSerializer<W> {
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write + embedded_io::Seek> SeekSerializer for EmbeddedIoSerializer<W> {
     fn seek(&mut self, offset: usize) -> Result<(), Self::Error> {
-        self.inner.seek(io::SeekFrom::Start(offset as u64))?;
+        self.inner
+            .seek(embedded_io::SeekFrom::Start(offset as u64))
+            .map_err(EmbeddedIoSerializerError::Io)?;
         self.pos = offset;
         Ok(())
     }
 }
+
+/// A serializer that can allocate temporary scratch space.
+///
+/// Scratch space is useful for serializing types that need to buffer some
+/// data before writing it out to the underlying [`Serializer`] in its final,
+/// contiguous form (for example, collections that are resolved out of order,
+/// or shared pointers that defer writing their pointee). Scratch allocations
+/// must be returned in LIFO order, the same way a stack allocator works.
+pub trait ScratchSpace: Fallible {
+    /// Allocates scratch space of the requested size and alignment.
+    ///
+    /// # Safety
+    ///
+    /// The returned scratch space must be deallocated by a call to
+    /// [`pop_scratch`](ScratchSpace::pop_scratch) with the same `layout`
+    /// before this scratch space (or the serializer it belongs to) is
+    /// dropped.
+    unsafe fn push_scratch(
+        &mut self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, Self::Error>;
+
+    /// Deallocates the given scratch space.
+    ///
+    /// # Safety
+    ///
+    /// The given `ptr` and `layout` must be the same ones returned by a call
+    /// to [`push_scratch`](ScratchSpace::push_scratch), and scratch space
+    /// must be popped in the reverse of the order it was pushed.
+    unsafe fn pop_scratch(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<(), Self::Error>;
+}
+
+/// A [`ScratchSpace`] backed by a fixed-size, inline buffer.
+///
+/// Because the buffer is inline, `FixedScratch` never allocates and can be
+/// used in `#![no_std]` environments without an allocator. Requesting more
+/// scratch space than the buffer has room for results in an
+/// [`Overflow`](FixedScratchError::Overflow) error.
+///
+/// ## Examples
+/// ```
+/// use core::{alloc::Layout, ptr::NonNull};
+/// use rkyv::ser::serializers::{FixedScratch, ScratchSpace};
+///
+/// let mut scratch = FixedScratch::<256>::new();
+/// let layout = Layout::new::<[u8; 16]>();
+/// unsafe {
+///     let allocation = scratch
+///         .push_scratch(layout)
+///         .expect("failed to allocate scratch space");
+///     let ptr = NonNull::new(allocation.as_ptr() as *mut u8).unwrap();
+///     scratch
+///         .pop_scratch(ptr, layout)
+///         .expect("failed to free scratch space");
+/// }
+/// ```
+pub struct FixedScratch<const N: usize> {
+    bytes: [core::mem::MaybeUninit<u8>; N],
+    len: usize,
+}
+
+impl<const N: usize> FixedScratch<N> {
+    /// Creates a new, empty `FixedScratch`.
+    pub fn new() -> Self {
+        Self {
+            // SAFETY: An uninitialized array of `MaybeUninit`s is always valid.
+            bytes: unsafe { core::mem::MaybeUninit::uninit().assume_init() },
+            len: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for FixedScratch<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The error type returned by a [`FixedScratch`].
+#[derive(Debug)]
+pub enum FixedScratchError {
+    /// Scratch space was requested past the end of the fixed buffer.
+    Overflow {
+        /// The number of bytes requested.
+        bytes_needed: usize,
+        /// The number of bytes free in the scratch buffer.
+        bytes_free: usize,
+    },
+    /// Scratch space was popped out of order.
+    NotPoppedInReverseOrder,
+}
+
+impl<const N: usize> Fallible for FixedScratch<N> {
+    type Error = FixedScratchError;
+}
+
+impl<const N: usize> ScratchSpace for FixedScratch<N> {
+    unsafe fn push_scratch(
+        &mut self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, Self::Error> {
+        let start = self.bytes.as_mut_ptr().cast::<u8>();
+        let offset = start.add(self.len).align_offset(layout.align());
+        let len = self.len + offset + layout.size();
+        if len > N {
+            Err(FixedScratchError::Overflow {
+                bytes_needed: layout.size(),
+                bytes_free: N - self.len,
+            })
+        } else {
+            let ptr = start.add(self.len + offset);
+            self.len = len;
+            Ok(NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(
+                ptr,
+                layout.size(),
+            )))
+        }
+    }
+
+    unsafe fn pop_scratch(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<(), Self::Error> {
+        let start = self.bytes.as_mut_ptr().cast::<u8>();
+        if ptr.as_ptr().add(layout.size()) as usize != start.add(self.len) as usize {
+            Err(FixedScratchError::NotPoppedInReverseOrder)
+        } else {
+            self.len = (ptr.as_ptr() as usize) - (start as usize);
+            Ok(())
+        }
+    }
+}
+
+/// A [`ScratchSpace`] that allocates scratch space from the global allocator.
+///
+/// Scratch space is returned to the allocator as soon as it is popped, so
+/// `AllocScratch` is a good default choice when an allocator is available but
+/// the maximum amount of scratch space needed isn't known up front.
+///
+/// ## Examples
+/// ```
+/// use core::{alloc::Layout, ptr::NonNull};
+/// use rkyv::ser::serializers::{AllocScratch, ScratchSpace};
+///
+/// let mut scratch = AllocScratch::default();
+/// let layout = Layout::new::<[u8; 16]>();
+/// unsafe {
+///     let allocation = scratch
+///         .push_scratch(layout)
+///         .expect("failed to allocate scratch space");
+///     let ptr = NonNull::new(allocation.as_ptr() as *mut u8).unwrap();
+///     scratch
+///         .pop_scratch(ptr, layout)
+///         .expect("failed to free scratch space");
+/// }
+/// ```
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[derive(Debug, Default)]
+pub struct AllocScratch {
+    // The address and layout of each outstanding allocation, in the order
+    // they were pushed. Checked against on `pop_scratch` to enforce LIFO order.
+    pushed: Vec<(usize, Layout)>,
+}
+
+/// The error type returned by an [`AllocScratch`].
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[derive(Debug)]
+pub enum AllocScratchError {
+    /// The allocator failed to allocate scratch space for the given layout.
+    AllocationFailed(Layout),
+    /// Scratch space was popped out of order.
+    NotPoppedInReverseOrder,
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl Fallible for AllocScratch {
+    type Error = AllocScratchError;
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl ScratchSpace for AllocScratch {
+    unsafe fn push_scratch(
+        &mut self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, Self::Error> {
+        // Calling the global allocator with a zero-sized layout is UB, so
+        // hand back a dangling, correctly-aligned pointer instead.
+        let ptr = if layout.size() == 0 {
+            layout.align() as *mut u8
+        } else {
+            let ptr = alloc(layout);
+            if ptr.is_null() {
+                return Err(AllocScratchError::AllocationFailed(layout));
+            }
+            ptr
+        };
+        self.pushed.push((ptr as usize, layout));
+        Ok(NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(
+            ptr,
+            layout.size(),
+        )))
+    }
+
+    unsafe fn pop_scratch(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<(), Self::Error> {
+        if self.pushed.last() != Some(&(ptr.as_ptr() as usize, layout)) {
+            return Err(AllocScratchError::NotPoppedInReverseOrder);
+        }
+        self.pushed.pop();
+        if layout.size() != 0 {
+            dealloc(ptr.as_ptr(), layout);
+        }
+        Ok(())
+    }
+}
+
+/// The error type returned by a [`CompositeSerializer`].
+#[derive(Debug)]
+pub enum CompositeSerializerError<W, S> {
+    /// An error occurred while writing to the underlying [`Serializer`].
+    SerializerError(W),
+    /// An error occurred while allocating scratch space.
+    ScratchSpaceError(S),
+}
+
+/// A serializer built from a [`Serializer`] and a [`ScratchSpace`].
+///
+/// `CompositeSerializer` pairs a writer that only knows how to append bytes
+/// with a pluggable allocator for temporary scratch space, so serializers for
+/// types that need to buffer data before writing it out don't need to
+/// reimplement scratch allocation themselves.
+///
+/// ## Examples
+/// ```
+/// use rkyv::{
+///     ser::{
+///         serializers::{BufferSerializer, CompositeSerializer, FixedScratch},
+///         Serializer,
+///     },
+///     Aligned,
+///     Archive,
+///     Serialize,
+/// };
+///
+/// #[derive(Archive, Serialize)]
+/// struct Example {
+///     a: i32,
+///     b: String,
+/// }
+///
+/// let mut serializer = CompositeSerializer::new(
+///     BufferSerializer::new(Aligned([0u8; 256])),
+///     FixedScratch::<256>::new(),
+/// );
+/// let value = Example { a: 42, b: "hello world".to_string() };
+/// serializer.serialize_value(&value).expect("failed to archive value");
+/// ```
+pub struct CompositeSerializer<W, S> {
+    serializer: W,
+    scratch: S,
+}
+
+impl<W, S> CompositeSerializer<W, S> {
+    /// Creates a new `CompositeSerializer` from a serializer and a scratch
+    /// space.
+    pub fn new(serializer: W, scratch: S) -> Self {
+        Self { serializer, scratch }
+    }
+
+    /// Consumes the `CompositeSerializer` and returns the underlying
+    /// serializer and scratch space.
+    pub fn into_components(self) -> (W, S) {
+        (self.serializer, self.scratch)
+    }
+
+    /// Consumes the `CompositeSerializer` and returns the underlying
+    /// serializer, discarding the scratch space.
+    pub fn into_serializer(self) -> W {
+        self.serializer
+    }
+}
+
+impl<W: Fallible, S: Fallible> Fallible for CompositeSerializer<W, S> {
+    type Error = CompositeSerializerError<W::Error, S::Error>;
+}
+
+impl<W: Serializer, S: Fallible> Serializer for CompositeSerializer<W, S> {
+    fn pos(&self) -> usize {
+        self.serializer.pos()
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.serializer
+            .write(bytes)
+            .map_err(CompositeSerializerError::SerializerError)
+    }
+
+    fn pad(&mut self, padding: usize) -> Result<(), Self::Error> {
+        self.serializer
+            .pad(padding)
+            .map_err(CompositeSerializerError::SerializerError)
+    }
+}
+
+impl<W: SeekSerializer, S: Fallible> SeekSerializer for CompositeSerializer<W, S> {
+    fn seek(&mut self, pos: usize) -> Result<(), Self::Error> {
+        self.serializer
+            .seek(pos)
+            .map_err(CompositeSerializerError::SerializerError)
+    }
+}
+
+impl<W: Fallible, S: ScratchSpace> ScratchSpace for CompositeSerializer<W, S> {
+    unsafe fn push_scratch(
+        &mut self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, Self::Error> {
+        self.scratch
+            .push_scratch(layout)
+            .map_err(CompositeSerializerError::ScratchSpaceError)
+    }
+
+    unsafe fn pop_scratch(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<(), Self::Error> {
+        self.scratch
+            .pop_scratch(ptr, layout)
+            .map_err(CompositeSerializerError::ScratchSpaceError)
+    }
+}
+
+/// A serializer that dispatches to one of two underlying serializers.
+///
+/// This is useful when a single concrete serializer type is needed, but the
+/// choice of which serializer to use can only be made at runtime -- for
+/// example, picking between a stack-allocated [`BufferSerializer`] for small
+/// values and a heap-allocated [`WriteSerializer`] for large ones. After
+/// serializing, the caller can `match` on the `Either` and call
+/// `into_inner()` on whichever variant was actually used.
+///
+/// ## Examples
+/// ```
+/// use rkyv::{
+///     ser::{
+///         serializers::{BufferSerializer, Either, WriteSerializer},
+///         Serializer,
+///     },
+///     Aligned,
+///     Archive,
+///     Serialize,
+/// };
+///
+/// #[derive(Archive, Serialize)]
+/// struct Example {
+///     a: i32,
+/// }
+///
+/// let value = Example { a: 42 };
+/// let fits_on_the_stack = true;
+/// let mut serializer = if fits_on_the_stack {
+///     Either::A(BufferSerializer::new(Aligned([0u8; 256])))
+/// } else {
+///     Either::B(WriteSerializer::new(Vec::new()))
+/// };
+/// serializer.serialize_value(&value).expect("failed to archive value");
+/// ```
+pub enum Either<A, B> {
+    /// The first serializer variant.
+    A(A),
+    /// The second serializer variant.
+    B(B),
+}
+
+impl<A: Fallible, B: Fallible> Fallible for Either<A, B> {
+    type Error = Either<A::Error, B::Error>;
+}
+
+impl<A: Serializer, B: Serializer> Serializer for Either<A, B> {
+    fn pos(&self) -> usize {
+        match self {
+            Either::A(a) => a.pos(),
+            Either::B(b) => b.pos(),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        match self {
+            Either::A(a) => a.write(bytes).map_err(Either::A),
+            Either::B(b) => b.write(bytes).map_err(Either::B),
+        }
+    }
+
+    fn pad(&mut self, padding: usize) -> Result<(), Self::Error> {
+        match self {
+            Either::A(a) => a.pad(padding).map_err(Either::A),
+            Either::B(b) => b.pad(padding).map_err(Either::B),
+        }
+    }
+}
+
+impl<A: SeekSerializer, B: SeekSerializer> SeekSerializer for Either<A, B> {
+    fn seek(&mut self, pos: usize) -> Result<(), Self::Error> {
+        match self {
+            Either::A(a) => a.seek(pos).map_err(Either::A),
+            Either::B(b) => b.seek(pos).map_err(Either::B),
+        }
+    }
+}
+
+/// Wraps a growable, always-aligned byte buffer and equips it with
+/// [`Serializer`].
+///
+/// Unlike [`BufferSerializer`], a `GrowableSerializer` never fails to write
+/// because it doesn't overflow -- it grows its backing [`AlignedVec`] on
+/// demand instead, and that growth is guaranteed to preserve the alignment
+/// rkyv requires. It also supports reserving space for a header whose
+/// contents (a length, a checksum, a root position) are only known once the
+/// body has been fully serialized: call [`reserve_prefix`](Self::reserve_prefix)
+/// before serializing the body, then [`write_prefix`](Self::write_prefix)
+/// to fill the reservation in afterwards.
+///
+/// ## Examples
+/// ```
+/// use rkyv::ser::{serializers::GrowableSerializer, Serializer};
+///
+/// // Reserve 4 bytes for a little-endian length header.
+/// let mut serializer = GrowableSerializer::new();
+/// serializer.reserve_prefix(4).expect("failed to reserve prefix");
+/// serializer.write(&[1u8, 2u8, 3u8]).expect("failed to write body");
+///
+/// let body_len = serializer.pos() as u32 - 4;
+/// serializer
+///     .write_prefix(0, &body_len.to_le_bytes())
+///     .expect("failed to patch prefix");
+///
+/// let archive = serializer.into_inner();
+/// assert_eq!(&archive[..4], &3u32.to_le_bytes());
+/// assert_eq!(&archive[4..], &[1u8, 2u8, 3u8]);
+/// ```
+#[cfg(any(feature = "std", feature = "alloc"))]
+pub struct GrowableSerializer {
+    inner: AlignedVec,
+    pos: usize,
+    reserved: usize,
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl GrowableSerializer {
+    /// Creates a new, empty `GrowableSerializer`.
+    pub fn new() -> Self {
+        Self {
+            inner: AlignedVec::new(),
+            pos: 0,
+            reserved: 0,
+        }
+    }
+
+    /// Reserves `len` bytes at the front of the archive for a header to be
+    /// filled in later with [`write_prefix`](Self::write_prefix).
+    ///
+    /// This must be called before anything else is written, so that the
+    /// reserved region stays at the front of the archive.
+    pub fn reserve_prefix(
+        &mut self,
+        len: usize,
+    ) -> Result<(), <Self as Fallible>::Error> {
+        if self.pos != 0 || self.inner.len() != 0 {
+            return Err(GrowableSerializerError::PrefixReservedAfterWrite);
+        }
+        for _ in 0..len {
+            self.inner.push(0);
+        }
+        self.reserved = len;
+        self.pos += len;
+        Ok(())
+    }
+
+    /// Returns the range of the archive reserved by
+    /// [`reserve_prefix`](Self::reserve_prefix).
+    pub fn reserved_range(&self) -> core::ops::Range<usize> {
+        0..self.reserved
+    }
+
+    /// Writes `bytes` into the region reserved by
+    /// [`reserve_prefix`](Self::reserve_prefix), starting at `offset`.
+    pub fn write_prefix(
+        &mut self,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<(), <Self as Fallible>::Error> {
+        let end = offset + bytes.len();
+        if end > self.reserved {
+            Err(GrowableSerializerError::PrefixOverflow {
+                offset,
+                bytes_needed: bytes.len(),
+                reserved_len: self.reserved,
+            })
+        } else {
+            self.inner.as_mut_slice()[offset..end].copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    /// Consumes the serializer and returns the internal buffer used to
+    /// create it.
+    pub fn into_inner(self) -> AlignedVec {
+        self.inner
+    }
+
+    /// Grows the backing buffer so it is at least `len` bytes long.
+    ///
+    /// Seeking backward and then writing or padding (e.g. to patch a
+    /// reserved prefix) must not append past the end of the archive, so
+    /// `write`/`pad` grow the buffer up to the target position instead of
+    /// blindly extending it.
+    fn grow_to(&mut self, len: usize) {
+        while self.inner.len() < len {
+            self.inner.push(0);
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl Default for GrowableSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The error type returned by a [`GrowableSerializer`].
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[derive(Debug)]
+pub enum GrowableSerializerError {
+    /// A write to a reserved prefix region overran the reservation.
+    PrefixOverflow {
+        /// The offset the write started at.
+        offset: usize,
+        /// The number of bytes the write needed.
+        bytes_needed: usize,
+        /// The length of the reserved region.
+        reserved_len: usize,
+    },
+    /// The serializer sought past the end of the archive.
+    SoughtPastEnd {
+        /// The position that was sought to.
+        seek_position: usize,
+        /// The length of the archive at the time of the seek.
+        archive_len: usize,
+    },
+    /// [`reserve_prefix`](GrowableSerializer::reserve_prefix) was called
+    /// after the archive had already been written to.
+    PrefixReservedAfterWrite,
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl Fallible for GrowableSerializer {
+    type Error = GrowableSerializerError;
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl Serializer for GrowableSerializer {
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        let end_pos = self.pos + bytes.len();
+        self.grow_to(end_pos);
+        self.inner.as_mut_slice()[self.pos..end_pos].copy_from_slice(bytes);
+        self.pos = end_pos;
+        Ok(())
+    }
+
+    fn pad(&mut self, padding: usize) -> Result<(), Self::Error> {
+        let end_pos = self.pos + padding;
+        self.grow_to(end_pos);
+        for byte in &mut self.inner.as_mut_slice()[self.pos..end_pos] {
+            *byte = 0;
+        }
+        self.pos = end_pos;
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl SeekSerializer for GrowableSerializer {
+    fn seek(&mut self, pos: usize) -> Result<(), Self::Error> {
+        let len = self.inner.len();
+        if pos > len {
+            Err(GrowableSerializerError::SoughtPastEnd {
+                seek_position: pos,
+                archive_len: len,
+            })
+        } else {
+            self.pos = pos;
+            Ok(())
+        }
+    }
+}
+
+/// The default serializer stack used by [`to_bytes`] and [`to_writer`].
+#[cfg(any(feature = "std", feature = "alloc"))]
+pub type AllocSerializer<W> = CompositeSerializer<W, AllocScratch>;
+
+/// Serializes the given value and returns the serialized [`AlignedVec`].
+///
+/// This is a convenience function that wires up the default serializer
+/// stack (a growable, always-aligned buffer paired with heap-allocated
+/// scratch space) so that the common case of serializing a single value
+/// doesn't require constructing a serializer by hand.
+///
+/// This is re-exported as `rkyv::to_bytes` at the crate root; reach for
+/// that path rather than this one.
+///
+/// ## Examples
+/// ```
+/// use rkyv::{ser::serializers::to_bytes, Archive, Serialize};
+///
+/// #[derive(Archive, Serialize)]
+/// struct Example {
+///     a: i32,
+///     b: String,
+/// }
+///
+/// let value = Example { a: 42, b: "hello world".to_string() };
+/// let bytes = to_bytes(&value).expect("failed to serialize");
+/// ```
+#[cfg(any(feature = "std", feature = "alloc"))]
+pub fn to_bytes<T>(
+    value: &T,
+) -> Result<AlignedVec, <AllocSerializer<GrowableSerializer> as Fallible>::Error>
+where
+    T: Serialize<AllocSerializer<GrowableSerializer>>,
+{
+    let mut serializer =
+        AllocSerializer::new(GrowableSerializer::new(), AllocScratch::default());
+    serializer.serialize_value(value)?;
+    Ok(serializer.into_components().0.into_inner())
+}
+
+/// Serializes the given value into the given writer using the default
+/// serializer stack, and returns the position of the serialized root.
+///
+/// This is the streaming analogue of [`to_bytes`], for when the serialized
+/// archive should be written directly to a [`Write`](io::Write) sink
+/// instead of collected into memory. Like `to_bytes`, it is re-exported as
+/// `rkyv::to_writer` at the crate root.
+#[cfg(feature = "std")]
+pub fn to_writer<W, T>(
+    writer: W,
+    value: &T,
+) -> Result<usize, <AllocSerializer<WriteSerializer<W>> as Fallible>::Error>
+where
+    W: io::Write,
+    T: Serialize<AllocSerializer<WriteSerializer<W>>>,
+{
+    let mut serializer =
+        AllocSerializer::new(WriteSerializer::new(writer), AllocScratch::default());
+    serializer.serialize_value(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_scratch_pushes_and_pops_in_lifo_order() {
+        let mut scratch = FixedScratch::<64>::new();
+        let layout = Layout::new::<[u8; 16]>();
+        unsafe {
+            let a = scratch.push_scratch(layout).unwrap();
+            let b = scratch.push_scratch(layout).unwrap();
+            let a_ptr = NonNull::new(a.as_ptr() as *mut u8).unwrap();
+            let b_ptr = NonNull::new(b.as_ptr() as *mut u8).unwrap();
+
+            assert!(matches!(
+                scratch.pop_scratch(a_ptr, layout),
+                Err(FixedScratchError::NotPoppedInReverseOrder)
+            ));
+
+            scratch.pop_scratch(b_ptr, layout).unwrap();
+            scratch.pop_scratch(a_ptr, layout).unwrap();
+        }
+    }
+
+    #[test]
+    fn fixed_scratch_overflows_past_its_capacity() {
+        let mut scratch = FixedScratch::<16>::new();
+        let layout = Layout::new::<[u8; 32]>();
+        unsafe {
+            assert!(matches!(
+                scratch.push_scratch(layout),
+                Err(FixedScratchError::Overflow { .. })
+            ));
+        }
+    }
+
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[test]
+    fn alloc_scratch_allows_zero_sized_allocations() {
+        let mut scratch = AllocScratch::default();
+        let layout = Layout::new::<()>();
+        unsafe {
+            let allocation = scratch.push_scratch(layout).unwrap();
+            assert_eq!(allocation.len(), 0);
+            let ptr = NonNull::new(allocation.as_ptr() as *mut u8).unwrap();
+            scratch.pop_scratch(ptr, layout).unwrap();
+        }
+    }
+
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[test]
+    fn alloc_scratch_rejects_out_of_order_pops() {
+        let mut scratch = AllocScratch::default();
+        let layout = Layout::new::<[u8; 16]>();
+        unsafe {
+            let a = scratch.push_scratch(layout).unwrap();
+            let _b = scratch.push_scratch(layout).unwrap();
+            let a_ptr = NonNull::new(a.as_ptr() as *mut u8).unwrap();
+
+            assert!(matches!(
+                scratch.pop_scratch(a_ptr, layout),
+                Err(AllocScratchError::NotPoppedInReverseOrder)
+            ));
+        }
+    }
+
+    #[test]
+    fn composite_serializer_surfaces_scratch_space_errors() {
+        let mut serializer = CompositeSerializer::new(
+            BufferSerializer::new([0u8; 16]),
+            FixedScratch::<8>::new(),
+        );
+        unsafe {
+            assert!(matches!(
+                serializer.push_scratch(Layout::new::<[u8; 16]>()),
+                Err(CompositeSerializerError::ScratchSpaceError(
+                    FixedScratchError::Overflow { .. }
+                ))
+            ));
+        }
+    }
+
+    #[test]
+    fn composite_serializer_surfaces_serializer_errors() {
+        let mut serializer = CompositeSerializer::new(
+            BufferSerializer::new([0u8; 2]),
+            FixedScratch::<8>::new(),
+        );
+        assert!(matches!(
+            serializer.write(&[1, 2, 3]),
+            Err(CompositeSerializerError::SerializerError(
+                SliceBufferError::Overflow { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn either_dispatches_to_the_active_variant() {
+        let mut a: Either<BufferSerializer<[u8; 4]>, BufferSerializer<[u8; 4]>> =
+            Either::A(BufferSerializer::new([0u8; 4]));
+        a.write(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(a.pos(), 4);
+
+        let mut b: Either<BufferSerializer<[u8; 4]>, BufferSerializer<[u8; 4]>> =
+            Either::B(BufferSerializer::new([0u8; 4]));
+        b.write(&[5, 6, 7, 8]).unwrap();
+        assert_eq!(b.pos(), 4);
+    }
+
+    #[test]
+    fn either_wraps_the_active_variants_error_in_the_matching_arm() {
+        let mut a: Either<BufferSerializer<[u8; 2]>, BufferSerializer<[u8; 2]>> =
+            Either::A(BufferSerializer::new([0u8; 2]));
+        assert!(matches!(
+            a.write(&[1, 2, 3]),
+            Err(Either::A(SliceBufferError::Overflow { .. }))
+        ));
+
+        let mut b: Either<BufferSerializer<[u8; 2]>, BufferSerializer<[u8; 2]>> =
+            Either::B(BufferSerializer::new([0u8; 2]));
+        assert!(matches!(
+            b.write(&[1, 2, 3]),
+            Err(Either::B(SliceBufferError::Overflow { .. }))
+        ));
+    }
+
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[test]
+    fn growable_serializer_patches_prefix_after_reserving() {
+        let mut serializer = GrowableSerializer::new();
+        serializer.reserve_prefix(4).unwrap();
+        serializer.write(&[1u8, 2u8, 3u8]).unwrap();
+
+        let body_len = serializer.pos() as u32 - 4;
+        serializer
+            .write_prefix(0, &body_len.to_le_bytes())
+            .unwrap();
+
+        let archive = serializer.into_inner();
+        assert_eq!(&archive[..4], &3u32.to_le_bytes());
+        assert_eq!(&archive[4..], &[1u8, 2u8, 3u8]);
+    }
+
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[test]
+    fn growable_serializer_rejects_reserve_prefix_after_writing() {
+        let mut serializer = GrowableSerializer::new();
+        serializer.write(&[1u8]).unwrap();
+        assert!(matches!(
+            serializer.reserve_prefix(4),
+            Err(GrowableSerializerError::PrefixReservedAfterWrite)
+        ));
+    }
+
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[test]
+    fn growable_serializer_seek_then_write_patches_in_place() {
+        let mut serializer = GrowableSerializer::new();
+        serializer.write(&[0u8; 8]).unwrap();
+        serializer.seek(2).unwrap();
+        serializer.write(&[1u8, 2u8]).unwrap();
+
+        let archive = serializer.into_inner();
+        assert_eq!(&archive[..], [0u8, 0u8, 1u8, 2u8, 0u8, 0u8, 0u8, 0u8]);
+    }
+
+    #[test]
+    fn buffer_serializer_writes_into_the_wrapped_slice_buffer() {
+        let mut serializer = BufferSerializer::new([0u8; 4]);
+        serializer.write(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(serializer.pos(), 4);
+        assert_eq!(serializer.into_inner().into_inner(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn buffer_serializer_overflow_errors_instead_of_corrupting_memory() {
+        let mut serializer = BufferSerializer::new([0u8; 2]);
+        assert!(matches!(
+            serializer.write(&[1, 2, 3]),
+            Err(SliceBufferError::Overflow { .. })
+        ));
+    }
+
+    #[test]
+    fn buffer_serializer_seek_then_write_patches_in_place() {
+        let mut serializer = BufferSerializer::new([0u8; 4]);
+        serializer.write(&[1, 2, 3, 4]).unwrap();
+        serializer.seek(1).unwrap();
+        serializer.write(&[9]).unwrap();
+        assert_eq!(serializer.pos(), 2);
+        assert_eq!(serializer.into_inner().into_inner(), [1, 9, 3, 4]);
+    }
+
+    #[test]
+    fn buffer_serializer_seek_past_end_errors() {
+        let mut serializer = BufferSerializer::new([0u8; 4]);
+        assert!(matches!(
+            serializer.seek(5),
+            Err(SliceBufferError::SoughtPastEnd { .. })
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_serializer_appends_to_the_wrapped_writer() {
+        let mut serializer = WriteSerializer::new(Vec::new());
+        serializer.write(&[1, 2, 3]).unwrap();
+        assert_eq!(serializer.pos(), 3);
+        assert_eq!(serializer.into_inner(), vec![1, 2, 3]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_serializer_seek_then_write_patches_the_wrapped_writer() {
+        let mut bytes = vec![0u8; 4];
+        {
+            let mut serializer =
+                WriteSerializer::new(std::io::Cursor::new(&mut bytes));
+            serializer.write(&[1, 2, 3, 4]).unwrap();
+            serializer.seek(1).unwrap();
+            serializer.write(&[9]).unwrap();
+            assert_eq!(serializer.pos(), 2);
+        }
+        assert_eq!(bytes, vec![1, 9, 3, 4]);
+    }
+
+    #[cfg(feature = "embedded-io")]
+    #[test]
+    fn embedded_io_serializer_errors_on_persistent_zero_progress() {
+        let mut bytes = [0u8; 4];
+        let mut serializer = EmbeddedIoSerializer::new(&mut bytes[..]);
+        serializer.write(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(serializer.pos(), 4);
+
+        assert!(matches!(
+            serializer.write(&[5]),
+            Err(EmbeddedIoSerializerError::WriteZero)
+        ));
+    }
+
+    #[cfg(feature = "embedded-io")]
+    struct FlakyWriter {
+        written: Vec<u8>,
+        should_block: bool,
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded_io::ErrorType for FlakyWriter {
+        type Error = embedded_io::ErrorKind;
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded_io::Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            if self.should_block {
+                self.should_block = false;
+                return Err(embedded_io::ErrorKind::WouldBlock);
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    #[cfg(feature = "embedded-io")]
+    #[test]
+    fn embedded_io_serializer_retries_after_would_block_without_corrupting_pos() {
+        let mut serializer = EmbeddedIoSerializer::new(FlakyWriter {
+            written: Vec::new(),
+            should_block: true,
+        });
+        serializer.write(&[1, 2, 3]).unwrap();
+        assert_eq!(serializer.pos(), 3);
+        assert_eq!(serializer.into_inner().written, vec![1, 2, 3]);
+    }
+}